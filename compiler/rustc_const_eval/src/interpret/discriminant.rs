@@ -1,12 +1,42 @@
 //! Functions for reading and writing discriminants of multi-variant layouts (enums and coroutines).
 
 use rustc_middle::mir;
-use rustc_middle::ty::layout::{LayoutOf, PrimitiveExt};
-use rustc_middle::ty::{self, ScalarInt, Ty};
+use rustc_middle::ty::layout::{LayoutError, LayoutOf, PrimitiveExt};
+use rustc_middle::ty::{self, ScalarInt, Ty, TyCtxt};
 use rustc_target::abi::{self, TagEncoding};
 use rustc_target::abi::{VariantIdx, Variants};
 
-use super::{ImmTy, InterpCx, InterpResult, Machine, Readable, Scalar, Writeable};
+use super::{DummyMachine, ImmTy, InterpCx, InterpResult, Machine, Readable, Scalar, Writeable};
+
+/// Query provider for `tcx.tag_for_variant`. See that query's documentation for details.
+///
+/// Computing the tag only ever needs to look at layouts and do a bit of integer arithmetic on
+/// the tag, none of which requires a "real" `Machine`: hence we can run it on a throwaway
+/// `InterpCx` backed by [`DummyMachine`] and expose the result as a self-contained query,
+/// without forcing every caller to go through the const-eval interpreter.
+///
+/// Takes the caller's `ParamEnv` as part of the query key rather than assuming
+/// `ParamEnv::reveal_all()`: callers outside the interpreter (e.g. a transmutability check) may
+/// need to ask this question about a still-generic `ty`, and forcing `reveal_all()` on those
+/// would be unsound. Layout errors (the only way this can fail, since `tag_for_variant` never
+/// reaches a UB check) are propagated as a normal `LayoutError` instead of panicking, exactly
+/// like `tcx.layout_of` itself.
+pub fn tag_for_variant_provider<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    (param_env, ty, variant_index): (ty::ParamEnv<'tcx>, Ty<'tcx>, VariantIdx),
+) -> Result<Option<(ScalarInt, usize)>, &'tcx LayoutError<'tcx>> {
+    // Validate the layout up front so a not-yet-interpreter-checked `ty` (e.g. one a
+    // transmutability analysis is still exploring) surfaces as a normal `LayoutError` here,
+    // rather than as a panic once we reach into `InterpCx::tag_for_variant` below.
+    tcx.layout_of(param_env.and(ty))?;
+    let ecx = InterpCx::new(tcx, rustc_span::DUMMY_SP, param_env, DummyMachine);
+    Ok(ecx.tag_for_variant(ty, variant_index).unwrap_or_else(|err| {
+        span_bug!(
+            rustc_span::DUMMY_SP,
+            "`tag_for_variant` failed after `layout_of` succeeded for the same type: {err:?}"
+        )
+    }))
+}
 
 impl<'mir, 'tcx: 'mir, M: Machine<'mir, 'tcx>> InterpCx<'mir, 'tcx, M> {
     /// Writes the discriminant of the given variant.
@@ -28,7 +58,9 @@ impl<'mir, 'tcx: 'mir, M: Machine<'mir, 'tcx>> InterpCx<'mir, 'tcx, M> {
             throw_ub!(UninhabitedEnumVariantWritten(variant_index))
         }
 
-        match self.tag_for_variant(dest.layout().ty, variant_index)? {
+        let tag_for_variant =
+            self.tcx.tag_for_variant((self.param_env, dest.layout().ty, variant_index))?;
+        match tag_for_variant {
             Some((tag, tag_field)) => {
                 // No need to validate that the discriminant here because the
                 // `TyAndLayout::for_variant()` call earlier already checks the
@@ -51,7 +83,7 @@ impl<'mir, 'tcx: 'mir, M: Machine<'mir, 'tcx>> InterpCx<'mir, 'tcx, M> {
         }
     }
 
-    /// Read discriminant, return the runtime value as well as the variant index.
+    /// Read discriminant, return the variant index.
     /// Can also legally be called on non-enums (e.g. through the discriminant_value intrinsic)!
     ///
     /// Will never return an uninhabited variant.
@@ -60,6 +92,18 @@ impl<'mir, 'tcx: 'mir, M: Machine<'mir, 'tcx>> InterpCx<'mir, 'tcx, M> {
         &self,
         op: &impl Readable<'tcx, M::Provenance>,
     ) -> InterpResult<'tcx, VariantIdx> {
+        Ok(self.read_discriminant_value(op)?.1)
+    }
+
+    /// Read discriminant, return the runtime value as well as the variant index.
+    /// Can also legally be called on non-enums (e.g. through the discriminant_value intrinsic)!
+    ///
+    /// Will never return an uninhabited variant.
+    #[instrument(skip(self), level = "trace")]
+    pub fn read_discriminant_value(
+        &self,
+        op: &impl Readable<'tcx, M::Provenance>,
+    ) -> InterpResult<'tcx, (ImmTy<'tcx, M::Provenance>, VariantIdx)> {
         let ty = op.layout().ty;
         trace!("read_discriminant_value {:#?}", op.layout());
         // Get type and layout of the discriminant.
@@ -87,7 +131,8 @@ impl<'mir, 'tcx: 'mir, M: Machine<'mir, 'tcx>> InterpCx<'mir, 'tcx, M> {
                         throw_ub!(UninhabitedEnumVariantRead(index))
                     }
                 }
-                return Ok(index);
+                let discr_value = self.discriminant_for_variant(ty, index)?;
+                return Ok((discr_value, index));
             }
             Variants::Multiple { tag, ref tag_encoding, tag_field, .. } => {
                 (tag, tag_encoding, tag_field)
@@ -113,7 +158,7 @@ impl<'mir, 'tcx: 'mir, M: Machine<'mir, 'tcx>> InterpCx<'mir, 'tcx, M> {
         trace!("tag value: {}", tag_val);
 
         // Figure out which discriminant and variant this corresponds to.
-        let index = match *tag_encoding {
+        let (discr_value, index) = match *tag_encoding {
             TagEncoding::Direct => {
                 // Generate a specific error if `tag_val` is not an integer.
                 // (`tag_bits` itself is only used for error messages below.)
@@ -140,7 +185,7 @@ impl<'mir, 'tcx: 'mir, M: Machine<'mir, 'tcx>> InterpCx<'mir, 'tcx, M> {
                 }
                 .ok_or_else(|| err_ub!(InvalidTag(Scalar::from_uint(tag_bits, tag_layout.size))))?;
                 // Return the cast value, and the index.
-                index.0
+                (discr_val, index.0)
             }
             TagEncoding::Niche { untagged_variant, ref niche_variants, niche_start } => {
                 let tag_val = tag_val.to_scalar();
@@ -191,10 +236,16 @@ impl<'mir, 'tcx: 'mir, M: Machine<'mir, 'tcx>> InterpCx<'mir, 'tcx, M> {
                         }
                     }
                 };
-                // Compute the size of the scalar we need to return.
-                // No need to cast, because the variant index directly serves as discriminant and is
-                // encoded in the tag.
-                variant
+                // Compute the discriminant value. For the untagged variant we have to fetch it
+                // like `tag_for_variant` would (it's not simply encoded in the tag); for the
+                // niche variants, the variant index directly serves as discriminant, so no
+                // further decoding is needed.
+                let discr_value = if variant == untagged_variant {
+                    self.discriminant_for_variant(ty, variant)?
+                } else {
+                    ImmTy::from_uint(variant.as_u32(), discr_layout)
+                };
+                (discr_value, variant)
             }
         };
         // Reading the discriminant of an uninhabited variant is UB. This is the basis for the
@@ -203,7 +254,7 @@ impl<'mir, 'tcx: 'mir, M: Machine<'mir, 'tcx>> InterpCx<'mir, 'tcx, M> {
         if op.layout().for_variant(self, index).abi.is_uninhabited() {
             throw_ub!(UninhabitedEnumVariantRead(index))
         }
-        Ok(index)
+        Ok((discr_value, index))
     }
 
     pub fn discriminant_for_variant(
@@ -231,6 +282,10 @@ impl<'mir, 'tcx: 'mir, M: Machine<'mir, 'tcx>> InterpCx<'mir, 'tcx, M> {
     /// - `None` means that nothing needs to be done as the variant is encoded implicitly
     /// - `Some((val, field_idx))` means that the given integer value needs to be stored at the
     ///   given field index.
+    ///
+    /// This is also used as the implementation of the `tag_for_variant` query; see
+    /// [`tag_for_variant_provider`] for the `DummyMachine`-backed entry point that callers
+    /// outside the interpreter should use instead of reaching for a real `Machine`.
     pub(crate) fn tag_for_variant(
         &self,
         ty: Ty<'tcx>,
@@ -300,4 +355,88 @@ impl<'mir, 'tcx: 'mir, M: Machine<'mir, 'tcx>> InterpCx<'mir, 'tcx, M> {
             }
         }
     }
+
+    /// Given an enum or coroutine `ty` and a candidate tag value that was not necessarily read
+    /// from a live operand, determines which variant (if any) that bit pattern selects.
+    ///
+    /// This is a public counterpart to [`Self::read_discriminant`] for callers that only have a
+    /// raw tag value to work with (e.g. a transmutability/layout-validity analysis enumerating
+    /// the possible byte sequences a tag field could hold), rather than an actual place in
+    /// memory. It applies the same `Direct`/`Niche` decoding rules as
+    /// [`Self::read_discriminant_value`], but as its own copy of that logic: the two functions
+    /// do not share code.
+    ///
+    /// Returns `None` if the tag bits select no variant (a `Direct` tag matching no
+    /// discriminant, or an enum with no variants) or select an uninhabited variant.
+    pub fn variant_for_tag_bits(
+        &self,
+        ty: Ty<'tcx>,
+        tag_bits: u128,
+    ) -> InterpResult<'tcx, Option<VariantIdx>> {
+        let layout = self.layout_of(ty)?;
+        let (tag_scalar_layout, tag_encoding, _tag_field) = match layout.variants {
+            Variants::Single { index } => {
+                // Same special-casing `read_discriminant_value` does for `Variants::Single`:
+                // a 0-variant enum has no inhabited variant to report, and a single-variant one
+                // must still be checked for inhabitedness.
+                if ty.is_enum() {
+                    if matches!(ty.kind(), ty::Adt(def, ..) if def.variants().is_empty()) {
+                        return Ok(None);
+                    }
+                    if layout.for_variant(self, index).abi.is_uninhabited() {
+                        return Ok(None);
+                    }
+                }
+                return Ok(Some(index));
+            }
+            Variants::Multiple { tag, ref tag_encoding, tag_field, .. } => {
+                (tag, tag_encoding, tag_field)
+            }
+        };
+        let tag_layout = self.layout_of(tag_scalar_layout.primitive().to_int_ty(*self.tcx))?;
+        let discr_layout = self.layout_of(ty.discriminant_ty(*self.tcx))?;
+
+        let index = match *tag_encoding {
+            TagEncoding::Direct => {
+                // Same lookup `read_discriminant_value` does for `TagEncoding::Direct`, just
+                // skipping the "read a live tag value" step: the candidate bits *are* the tag.
+                // We still have to cast from `tag_layout` to `discr_layout` before comparing
+                // against `var.val`, since that cast sign-extends for signed tags narrower than
+                // the discriminant type (e.g. a packed `i8` tag but an `isize` discriminant).
+                let tag_val = ImmTy::from_uint(tag_layout.size.truncate(tag_bits), tag_layout);
+                let discr_val = self.int_to_int_or_float(&tag_val, discr_layout).unwrap();
+                let discr_bits = discr_val.to_scalar().assert_bits(discr_layout.size);
+                match *ty.kind() {
+                    ty::Adt(adt, _) => {
+                        adt.discriminants(*self.tcx).find(|(_, var)| var.val == discr_bits)
+                    }
+                    ty::Coroutine(def_id, args) => {
+                        let args = args.as_coroutine();
+                        args.discriminants(def_id, *self.tcx).find(|(_, var)| var.val == discr_bits)
+                    }
+                    _ => span_bug!(self.cur_span(), "tagged layout for non-adt non-coroutine"),
+                }
+                .map(|(index, _)| index)
+            }
+            TagEncoding::Niche { untagged_variant, ref niche_variants, niche_start } => {
+                let variants_start = niche_variants.start().as_u32();
+                let variants_end = niche_variants.end().as_u32();
+                let relative = tag_layout.size.truncate(tag_bits.wrapping_sub(niche_start));
+                if relative <= u128::from(variants_end - variants_start) {
+                    let relative =
+                        u32::try_from(relative).expect("we checked that this fits into a u32");
+                    Some(VariantIdx::from_u32(
+                        variants_start
+                            .checked_add(relative)
+                            .expect("overflow computing absolute variant idx"),
+                    ))
+                } else {
+                    Some(untagged_variant)
+                }
+            }
+        };
+
+        // Same UB-avoidance rule as `read_discriminant`: never report an uninhabited variant.
+        Ok(index.filter(|&index| !layout.for_variant(self, index).abi.is_uninhabited()))
+    }
 }
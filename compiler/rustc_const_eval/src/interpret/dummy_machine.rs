@@ -0,0 +1,159 @@
+//! A zero-state `Machine` used to run tag-computation logic (see the `discriminant` module)
+//! outside of a real interpreter session, e.g. as the backend for the `tag_for_variant` query.
+
+use rustc_middle::mir;
+use rustc_middle::mir::interpret::{AllocId, InterpResult};
+use rustc_middle::ty;
+use rustc_target::abi::Size;
+
+use super::{
+    throw_unsup, throw_unsup_format, Allocation, CtfeProvenance, FnArg, ImmTy, InterpCx, Machine,
+    MemoryKind, OpTy, Pointer,
+};
+
+/// A `Machine` with no state at all, for running just enough of the interpreter to compute tags
+/// and discriminants: no allocations, no foreign calls, no pointer-to-integer casts, just the
+/// integer arithmetic on tag values needed by `InterpCx::tag_for_variant` and
+/// `InterpCx::read_discriminant`. Anything beyond that is a bug in the caller: most unsupported
+/// operations below are an ICE, while pointer arithmetic, allocations and integer-to-pointer casts
+/// are reported as "unsupported" errors.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DummyMachine;
+
+impl<'mir, 'tcx: 'mir> Machine<'mir, 'tcx> for DummyMachine {
+    type MemoryKind = !;
+    type Provenance = CtfeProvenance;
+    type ExtraFnVal = !;
+    type FrameExtra = ();
+    type AllocExtra = ();
+    type Bytes = Box<[u8]>;
+    type MemoryMap = rustc_data_structures::fx::FxIndexMap<AllocId, (MemoryKind<!>, Allocation)>;
+
+    const PANIC_ON_ALLOC_FAIL: bool = true;
+
+    #[inline(always)]
+    fn enforce_alignment(_ecx: &InterpCx<'mir, 'tcx, Self>) -> bool {
+        false
+    }
+
+    #[inline(always)]
+    fn enforce_validity(
+        _ecx: &InterpCx<'mir, 'tcx, Self>,
+        _layout: ty::layout::TyAndLayout<'tcx>,
+    ) -> bool {
+        false
+    }
+
+    fn find_mir_or_eval_fn(
+        _ecx: &mut InterpCx<'mir, 'tcx, Self>,
+        _instance: ty::Instance<'tcx>,
+        _abi: rustc_target::spec::abi::Abi,
+        _args: &[FnArg<'tcx, Self::Provenance>],
+        _destination: &OpTy<'tcx, Self::Provenance>,
+        _target: Option<mir::BasicBlock>,
+        _unwind: mir::UnwindAction,
+    ) -> InterpResult<'tcx, Option<(&'mir mir::Body<'tcx>, ty::Instance<'tcx>)>> {
+        unreachable!("`DummyMachine` is only used for tag computations, it cannot call functions")
+    }
+
+    fn call_extra_fn(
+        _ecx: &mut InterpCx<'mir, 'tcx, Self>,
+        fn_val: !,
+        _abi: rustc_target::spec::abi::Abi,
+        _args: &[FnArg<'tcx, Self::Provenance>],
+        _destination: &OpTy<'tcx, Self::Provenance>,
+        _target: Option<mir::BasicBlock>,
+        _unwind: mir::UnwindAction,
+    ) -> InterpResult<'tcx> {
+        match fn_val {}
+    }
+
+    fn call_intrinsic(
+        _ecx: &mut InterpCx<'mir, 'tcx, Self>,
+        _instance: ty::Instance<'tcx>,
+        _args: &[OpTy<'tcx, Self::Provenance>],
+        _destination: &OpTy<'tcx, Self::Provenance>,
+        _target: Option<mir::BasicBlock>,
+        _unwind: mir::UnwindAction,
+    ) -> InterpResult<'tcx, Option<ty::Instance<'tcx>>> {
+        unreachable!("`DummyMachine` cannot evaluate intrinsics")
+    }
+
+    fn assert_panic(
+        _ecx: &mut InterpCx<'mir, 'tcx, Self>,
+        _msg: &mir::AssertMessage<'tcx>,
+        _unwind: mir::UnwindAction,
+    ) -> InterpResult<'tcx> {
+        unreachable!("`DummyMachine` never runs code that can trigger an assertion")
+    }
+
+    fn binary_ptr_op(
+        _ecx: &InterpCx<'mir, 'tcx, Self>,
+        _bin_op: mir::BinOp,
+        _left: &ImmTy<'tcx, Self::Provenance>,
+        _right: &ImmTy<'tcx, Self::Provenance>,
+    ) -> InterpResult<'tcx, ImmTy<'tcx, Self::Provenance>> {
+        throw_unsup_format!("pointer arithmetic is not supported by `DummyMachine`")
+    }
+
+    fn expose_ptr(_ecx: &mut InterpCx<'mir, 'tcx, Self>, _ptr: Pointer) -> InterpResult<'tcx> {
+        unreachable!("`DummyMachine` never creates pointers to expose")
+    }
+
+    fn init_frame_extra(
+        _ecx: &mut InterpCx<'mir, 'tcx, Self>,
+        _frame: mir::interpret::Frame<'mir, 'tcx, Self::Provenance>,
+    ) -> InterpResult<'tcx, mir::interpret::Frame<'mir, 'tcx, Self::Provenance, Self::FrameExtra>> {
+        unreachable!("`DummyMachine` never pushes a stack frame")
+    }
+
+    fn stack<'a>(
+        _ecx: &'a InterpCx<'mir, 'tcx, Self>,
+    ) -> &'a [mir::interpret::Frame<'mir, 'tcx, Self::Provenance, Self::FrameExtra>] {
+        &[]
+    }
+
+    fn stack_mut<'a>(
+        _ecx: &'a mut InterpCx<'mir, 'tcx, Self>,
+    ) -> &'a mut Vec<mir::interpret::Frame<'mir, 'tcx, Self::Provenance, Self::FrameExtra>> {
+        unreachable!("`DummyMachine` never pushes a stack frame")
+    }
+
+    fn thread_local_static_pointer(
+        _ecx: &mut InterpCx<'mir, 'tcx, Self>,
+        _def_id: rustc_hir::def_id::DefId,
+    ) -> InterpResult<'tcx, Pointer<Self::Provenance>> {
+        unreachable!("`DummyMachine` never accesses thread-local statics")
+    }
+
+    fn extern_static_pointer(
+        _ecx: &InterpCx<'mir, 'tcx, Self>,
+        _def_id: rustc_hir::def_id::DefId,
+    ) -> InterpResult<'tcx, Pointer<Self::Provenance>> {
+        unreachable!("`DummyMachine` never accesses extern statics")
+    }
+
+    fn adjust_allocation<'b>(
+        _ecx: &InterpCx<'mir, 'tcx, Self>,
+        _id: AllocId,
+        _alloc: std::borrow::Cow<'b, Allocation>,
+        _kind: Option<MemoryKind<Self::MemoryKind>>,
+    ) -> InterpResult<'tcx, std::borrow::Cow<'b, Allocation<Self::Provenance>>> {
+        // `DummyMachine` never reads through a pointer, so it never needs to allocate.
+        throw_unsup!(Unsupported("`DummyMachine` does not support allocations".to_string()))
+    }
+
+    fn ptr_from_addr_cast(
+        _ecx: &InterpCx<'mir, 'tcx, Self>,
+        _addr: u64,
+    ) -> InterpResult<'tcx, Pointer<Option<Self::Provenance>>> {
+        throw_unsup_format!("integer-to-pointer casts are not supported by `DummyMachine`")
+    }
+
+    fn ptr_get_alloc(
+        _ecx: &InterpCx<'mir, 'tcx, Self>,
+        _ptr: Pointer<Self::Provenance>,
+    ) -> Option<(AllocId, Size, Self::Provenance)> {
+        None
+    }
+}
@@ -0,0 +1,5 @@
+mod discriminant;
+mod dummy_machine;
+
+pub use discriminant::tag_for_variant_provider;
+pub use dummy_machine::DummyMachine;
@@ -0,0 +1,7 @@
+pub mod interpret;
+
+use rustc_middle::query::Providers;
+
+pub fn provide(providers: &mut Providers) {
+    providers.queries.tag_for_variant = interpret::tag_for_variant_provider;
+}
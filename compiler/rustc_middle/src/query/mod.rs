@@ -0,0 +1,12 @@
+rustc_queries! {
+    /// Computes how the tag for `key.2` (a variant index) of the enum or coroutine `key.1` is
+    /// encoded under the given `ParamEnv` (`key.0`), reusing the exact same `Direct`/`Niche`
+    /// rules the const-eval interpreter uses for `write_discriminant`. Returns `None` when the
+    /// variant is encoded implicitly (i.e. no tag needs to be written), and
+    /// `Some((tag, tag_field))` otherwise.
+    query tag_for_variant(
+        key: (ty::ParamEnv<'tcx>, Ty<'tcx>, VariantIdx)
+    ) -> Result<Option<(ty::ScalarInt, usize)>, &'tcx LayoutError<'tcx>> {
+        desc { "computing the tag for variant {} of `{}`", key.2.index(), key.1 }
+    }
+}